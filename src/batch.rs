@@ -0,0 +1,171 @@
+//! A circuit that attests to a whole *batch* of Merkle tree mutations with
+//! a single proof, threading the root through each mutation in turn.
+//!
+//! Each [`Step`] proves that some `old_leaf` sits at a path under
+//! `old_root`, and that `new_leaf` sits at the *same* path under
+//! `new_root` — i.e. it's a single-leaf update. An `Insert` is just the
+//! special case where `old_leaf` is [`non_membership::EMPTY_LEAF`]: the
+//! slot was empty before and holds the new leaf afterwards. Chaining
+//! `steps[i].new_root == steps[i + 1].old_root`, with `steps[0].old_root`
+//! pinned to the circuit's public `initial_root` and the last step's
+//! `new_root` pinned to `final_root`, lets one proof attest to an entire
+//! sequence of operations — useful for transparency logs or rollup state
+//! transitions, where batching amortizes proving cost across many leaves.
+//!
+//! A step witnesses a *single* `path`, shared between the pre- and
+//! post-state membership checks, rather than an independent `old_path`
+//! and `new_path`: the update only changes the leaf, not its position or
+//! siblings, so allocating one path and checking it against both roots is
+//! what actually enforces that `old_leaf` and `new_leaf` sit at the same
+//! place in the tree. Two independently-witnessed paths would let a
+//! prover point `old_leaf` and `new_leaf` at unrelated slots entirely.
+
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use r1cs_std::prelude::*;
+
+use algebra::fields::bls12_381::fr::Fr;
+use crypto_primitives::crh::{FixedLengthCRH, FixedLengthCRHGadget};
+
+use crate::non_membership::EMPTY_LEAF;
+use crate::wide_tree::{WideMerkleTreeConfig, WideMerkleTreeDigest, WideMerkleTreePath, WideMerkleTreePathGadget};
+
+/// A single Update-or-Insert mutation: `old_leaf` at `path` under
+/// `old_root` becomes `new_leaf` at the same `path` under `new_root`.
+#[derive(Clone)]
+pub struct Step<C: WideMerkleTreeConfig> {
+    pub old_leaf: [u8; 30],
+    pub new_leaf: [u8; 30],
+    pub old_root: WideMerkleTreeDigest<C>,
+    pub new_root: WideMerkleTreeDigest<C>,
+    pub path: WideMerkleTreePath<C>,
+}
+
+impl<C: WideMerkleTreeConfig> Step<C> {
+    /// `old_leaf` at `path` becomes `new_leaf` at the same `path`.
+    pub fn update(
+        old_leaf: [u8; 30],
+        new_leaf: [u8; 30],
+        old_root: WideMerkleTreeDigest<C>,
+        new_root: WideMerkleTreeDigest<C>,
+        path: WideMerkleTreePath<C>,
+    ) -> Self {
+        Self { old_leaf, new_leaf, old_root, new_root, path }
+    }
+
+    /// An empty slot under `pre_insertion_root` becomes `leaf` under
+    /// `post_insertion_root`.
+    pub fn insert(
+        leaf: [u8; 30],
+        pre_insertion_root: WideMerkleTreeDigest<C>,
+        post_insertion_root: WideMerkleTreeDigest<C>,
+        path: WideMerkleTreePath<C>,
+    ) -> Self {
+        Self::update(EMPTY_LEAF, leaf, pre_insertion_root, post_insertion_root, path)
+    }
+}
+
+impl<C: WideMerkleTreeConfig> Default for Step<C>
+where
+    WideMerkleTreeDigest<C>: Default,
+{
+    fn default() -> Self {
+        Self {
+            old_leaf: EMPTY_LEAF,
+            new_leaf: EMPTY_LEAF,
+            old_root: WideMerkleTreeDigest::<C>::default(),
+            new_root: WideMerkleTreeDigest::<C>::default(),
+            path: WideMerkleTreePath::<C>::default(),
+        }
+    }
+}
+
+/// BatchMerkleProofCircuit proves a sequence of [`Step`]s, each threading
+/// its root into the next, with `initial_root`/`final_root` as the only
+/// public inputs — the intermediate roots are witnesses.
+pub struct BatchMerkleProofCircuit<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> {
+    params: <C::H as FixedLengthCRH>::Parameters,
+    /// Part of instance or "public input"
+    initial_root: Option<WideMerkleTreeDigest<C>>,
+    /// Part of instance or "public input"
+    final_root: Option<WideMerkleTreeDigest<C>>,
+    /// Part of witness or "private input"
+    steps: Vec<Step<C>>,
+    _hash_gadget: std::marker::PhantomData<HG>,
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> BatchMerkleProofCircuit<C, HG>
+where
+    WideMerkleTreeDigest<C>: Default,
+{
+    pub fn for_setup(params: <C::H as FixedLengthCRH>::Parameters, num_steps: usize) -> Self {
+        Self {
+            params,
+            initial_root: None,
+            final_root: None,
+            steps: vec![Step::default(); num_steps],
+            _hash_gadget: std::marker::PhantomData,
+        }
+    }
+
+    pub fn for_proving(
+        params: <C::H as FixedLengthCRH>::Parameters,
+        initial_root: WideMerkleTreeDigest<C>,
+        final_root: WideMerkleTreeDigest<C>,
+        steps: Vec<Step<C>>,
+    ) -> Self {
+        Self { params, initial_root: Some(initial_root), final_root: Some(final_root), steps, _hash_gadget: std::marker::PhantomData }
+    }
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> ConstraintSynthesizer<Fr> for BatchMerkleProofCircuit<C, HG>
+where
+    HG::OutputGadget: CondSelectGadget<Fr>,
+{
+    fn generate_constraints<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let Self { params, initial_root, final_root, steps, .. } = self;
+
+        let initial_root = HG::OutputGadget::alloc_input(
+            &mut cs.ns(|| "Initial root"),
+            || initial_root.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let final_root = HG::OutputGadget::alloc_input(
+            &mut cs.ns(|| "Final root"),
+            || final_root.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let crh_parameters = HG::ParametersGadget::alloc(&mut cs.ns(|| "Parameters"), || Ok(params))?;
+
+        let mut running_root = initial_root;
+        for (i, step) in steps.into_iter().enumerate() {
+            let mut step_cs = cs.ns(|| format!("Step {}", i));
+
+            let old_root = HG::OutputGadget::alloc(step_cs.ns(|| "old root"), || Ok(step.old_root.clone()))?;
+            old_root.enforce_equal(&mut step_cs.ns(|| "chain from previous step"), &running_root)?;
+
+            // A single shared path, checked against both roots, is what
+            // ties `old_leaf` and `new_leaf` to the same position and
+            // sibling set.
+            let old_leaf = UInt8::alloc_vec(step_cs.ns(|| "old leaf"), &step.old_leaf)?;
+            let new_leaf = UInt8::alloc_vec(step_cs.ns(|| "new leaf"), &step.new_leaf)?;
+            let path = WideMerkleTreePathGadget::<C, HG>::alloc(step_cs.ns(|| "path"), &step.path)?;
+            path.check_membership(
+                &mut step_cs.ns(|| "check pre-state"),
+                &crh_parameters,
+                &old_root,
+                &old_leaf.as_slice(),
+            )?;
+
+            let new_root = HG::OutputGadget::alloc(step_cs.ns(|| "new root"), || Ok(step.new_root))?;
+            path.check_membership(
+                &mut step_cs.ns(|| "check post-state"),
+                &crh_parameters,
+                &new_root,
+                &new_leaf.as_slice(),
+            )?;
+
+            running_root = new_root;
+        }
+
+        running_root.enforce_equal(&mut cs.ns(|| "final root matches last step"), &final_root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! A "proof of succinct work" mode: on top of the usual membership check,
+//! the circuit also proves that a `nonce`-derived mask was correctly
+//! applied to the root, as in mineable/consensus-linked constructions.
+//!
+//! The unmasked `root` becomes a witness instead of a public input — the
+//! verifier only ever sees `nonce` and `masked_commitment`, and the circuit
+//! itself enforces `masked_commitment == Blake2s(nonce || root)`.
+//! A valid proof therefore attests to both "I know a leaf in a tree with
+//! some root" and "that root was committed under this specific nonce",
+//! without revealing the root itself.
+
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use r1cs_std::prelude::*;
+
+use algebra::fields::bls12_381::fr::Fr;
+use crypto_primitives::{
+    crh::{FixedLengthCRH, FixedLengthCRHGadget},
+    merkle_tree::*,
+    merkle_tree::constraints::*,
+    prf::{blake2s::Blake2s, blake2s::constraints::Blake2sGadget, PRF, PRFGadget},
+};
+
+/// `mask = Blake2s(nonce || root)`, computed natively via the `PRF` trait
+/// with `nonce` as the seed and `root`'s bytes as the input.
+pub fn compute_mask<C: MerkleTreeConfig>(nonce: &[u8; 32], root: &MerkleTreeDigest<C>) -> [u8; 32]
+where
+    MerkleTreeDigest<C>: algebra::ToBytes,
+{
+    let mut root_bytes = Vec::new();
+    root.write(&mut root_bytes).unwrap();
+    Blake2s::evaluate(nonce, &root_bytes).unwrap()
+}
+
+/// MaskedPathCheckCircuit proves membership of `leaf` in the tree rooted at
+/// a (secret) `root`, and that `masked_commitment` is `root` correctly
+/// masked by the public `nonce`.
+pub struct MaskedPathCheckCircuit<C: MerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> {
+    params: <C::H as FixedLengthCRH>::Parameters,
+    /// Part of witness or "private input": unlike `PathCheckCircuit`, the
+    /// root is no longer public.
+    leaf: Option<[u8; 30]>,
+    root: Option<MerkleTreeDigest<C>>,
+    path: MerkleTreePath<C>,
+    /// Part of instance or "public input"
+    nonce: Option<[u8; 32]>,
+    /// Part of instance or "public input"
+    masked_commitment: Option<[u8; 32]>,
+    _hash_gadget: std::marker::PhantomData<HG>,
+}
+
+impl<C: MerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> MaskedPathCheckCircuit<C, HG> {
+    pub fn for_setup(params: <C::H as FixedLengthCRH>::Parameters) -> Self {
+        Self {
+            params,
+            leaf: None,
+            root: None,
+            path: MerkleTreePath::<C>::default(),
+            nonce: None,
+            masked_commitment: None,
+            _hash_gadget: std::marker::PhantomData,
+        }
+    }
+
+    pub fn for_proving(
+        params: <C::H as FixedLengthCRH>::Parameters,
+        leaf: [u8; 30],
+        root: MerkleTreeDigest<C>,
+        path: MerkleTreePath<C>,
+        nonce: [u8; 32],
+        masked_commitment: [u8; 32],
+    ) -> Self {
+        Self {
+            params,
+            leaf: Some(leaf),
+            root: Some(root),
+            path,
+            nonce: Some(nonce),
+            masked_commitment: Some(masked_commitment),
+            _hash_gadget: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<C: MerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> ConstraintSynthesizer<Fr> for MaskedPathCheckCircuit<C, HG>
+where
+    HG::OutputGadget: ToBytesGadget<Fr>,
+{
+    fn generate_constraints<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let Self { params, leaf, root, path, nonce, masked_commitment, .. } = self;
+
+        let nonce = UInt8::alloc_input_vec(&mut cs.ns(|| "Nonce"), &nonce.unwrap_or([0u8; 32]))?;
+        let masked_commitment =
+            UInt8::alloc_input_vec(&mut cs.ns(|| "Masked commitment"), &masked_commitment.unwrap_or([0u8; 32]))?;
+
+        // The root is a witness here, not a public input.
+        let root = HG::OutputGadget::alloc(
+            &mut cs.ns(|| "Digest"),
+            || root.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        let leaf = UInt8::alloc_vec(&mut cs.ns(|| "Leaf"), &leaf.unwrap_or([0u8; 30]))?;
+        let crh_parameters = HG::ParametersGadget::alloc(&mut cs.ns(|| "Parameters"), || Ok(params))?;
+        let path = MerkleTreePathGadget::<_, HG, _>::alloc(&mut cs.ns(|| "Path"), || Ok(path))?;
+        path.check_membership(&mut cs.ns(|| "Check membership"), &crh_parameters, &root, &leaf.as_slice())?;
+
+        // mask = Blake2s(nonce || root), enforced equal to the claimed
+        // `masked_commitment`.
+        let root_bytes = root.to_bytes(&mut cs.ns(|| "Root bytes"))?;
+        let mask = <Blake2sGadget as PRFGadget<Blake2s, Fr>>::check_evaluation_gadget(
+            &mut cs.ns(|| "Blake2s(nonce, root)"),
+            &nonce,
+            &root_bytes,
+        )?;
+        for (i, (computed, claimed)) in mask.iter().zip(masked_commitment.iter()).enumerate() {
+            computed.enforce_equal(&mut cs.ns(|| format!("mask byte {} matches", i)), claimed)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Multipacking: fold a sequence of bits into the fewest possible `Fr`
+//! elements, `Fr::CAPACITY` bits per element, little-endian within each
+//! element. Used to shrink public-input vectors (see `PathCheckCircuit`'s
+//! packed leaf) and, natively, to absorb byte strings into the Poseidon
+//! sponge (see `poseidon::PoseidonCRH`).
+
+use algebra::fields::bls12_381::fr::Fr;
+use algebra::{Field, PrimeField};
+use r1cs_core::{ConstraintSystem, SynthesisError};
+use r1cs_std::fields::fp::FpGadget;
+use r1cs_std::prelude::*;
+
+fn bytes_to_bits(input: &[u8]) -> Vec<bool> {
+    input
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .collect()
+}
+
+fn pack_bit_chunk(chunk: &[bool]) -> Fr {
+    let mut acc = Fr::zero();
+    let mut coeff = Fr::one();
+    for bit in chunk {
+        if *bit {
+            acc += &coeff;
+        }
+        coeff.double_in_place();
+    }
+    acc
+}
+
+/// Packs `input` into the fewest possible `Fr` elements.
+pub fn pack_bytes(input: &[u8]) -> Vec<Fr> {
+    bytes_to_bits(input)
+        .chunks(<Fr as PrimeField>::CAPACITY as usize)
+        .map(pack_bit_chunk)
+        .collect()
+}
+
+/// In-circuit counterpart of [`pack_bytes`], operating on already-allocated
+/// bits. Each returned `FpGadget` is allocated as a *witness*, constrained
+/// to equal the weighted sum of its chunk of `bits`.
+pub fn pack_bits<CS: ConstraintSystem<Fr>>(mut cs: CS, bits: &[Boolean]) -> Result<Vec<FpGadget<Fr>>, SynthesisError> {
+    bits.chunks(<Fr as PrimeField>::CAPACITY as usize)
+        .enumerate()
+        .map(|(i, chunk)| pack_bit_chunk_gadget(cs.ns(|| format!("pack {}", i)), chunk, false))
+        .collect()
+}
+
+/// Like [`pack_bits`], but each packed value is allocated as a *public
+/// input* rather than a witness.
+pub fn pack_bits_as_input<CS: ConstraintSystem<Fr>>(
+    mut cs: CS,
+    bits: &[Boolean],
+) -> Result<Vec<FpGadget<Fr>>, SynthesisError> {
+    bits.chunks(<Fr as PrimeField>::CAPACITY as usize)
+        .enumerate()
+        .map(|(i, chunk)| pack_bit_chunk_gadget(cs.ns(|| format!("pack {}", i)), chunk, true))
+        .collect()
+}
+
+fn pack_bit_chunk_gadget<CS: ConstraintSystem<Fr>>(
+    mut cs: CS,
+    chunk: &[Boolean],
+    as_input: bool,
+) -> Result<FpGadget<Fr>, SynthesisError> {
+    let value = || -> Result<Fr, SynthesisError> {
+        let bits = chunk
+            .iter()
+            .map(|b| b.get_value().unwrap_or(false))
+            .collect::<Vec<_>>();
+        Ok(pack_bit_chunk(&bits))
+    };
+    let packed = if as_input {
+        FpGadget::alloc_input(cs.ns(|| "packed"), value)?
+    } else {
+        FpGadget::alloc(cs.ns(|| "packed"), value)?
+    };
+
+    // Enforce `packed == sum bit_i * 2^i` as a single R1CS constraint:
+    // `packed * 1 == sum_i bit_i * 2^i`.
+    cs.enforce(
+        || "packed == weighted sum of bits",
+        |lc| lc + packed.get_variable(),
+        |lc| lc + CS::one(),
+        |lc| {
+            let mut coeff = Fr::one();
+            chunk.iter().fold(lc, |lc, bit| {
+                let lc = lc + &bit.lc(CS::one(), coeff);
+                coeff.double_in_place();
+                lc
+            })
+        },
+    );
+
+    Ok(packed)
+}
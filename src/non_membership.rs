@@ -0,0 +1,157 @@
+//! Proof of *non-membership* in a sparse, key-indexed Merkle tree.
+//!
+//! `PathCheckCircuit` only proves a leaf *is* present at some path. This
+//! module adds the companion statement: a given key is *absent*. We model
+//! the tree as a binary [`WideMerkleTreeConfig`] (`ARITY = 2`) of
+//! `2^HEIGHT` slots, where a key `k` lives at slot `index_for_key(k)` and
+//! every slot that holds no real entry is populated with [`EMPTY_LEAF`].
+//! Proving non-membership of `k` then reduces to proving two things: that
+//! the empty leaf sits at the witnessed path (exactly `check_membership`
+//! run against a fixed, public leaf value instead of a witnessed one), *and*
+//! that the witnessed path's position really is `index_for_key(k)` — without
+//! the latter, a prover could point the path at any other empty slot and the
+//! proof would verify against any `key` public input.
+//!
+//! This is why the tree here is `wide_tree::WideMerkleTreeConfig` rather
+//! than `crypto_primitives::merkle_tree::MerkleTreeConfig`: the upstream
+//! `MerkleTreePathGadget` is opaque and never exposes the position it
+//! witnessed, so there is nothing to bind `index_for_key(key)` to. The local
+//! `WideMerkleTreePathGadget` tracks the position as explicit bits
+//! (`index_bits`), so the circuit can recompute `index_for_key(key)` itself
+//! and enforce bit-for-bit equality against them.
+
+use std::rc::Rc;
+
+use algebra::fields::bls12_381::fr::Fr;
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use r1cs_std::prelude::*;
+
+use crypto_primitives::crh::{FixedLengthCRH, FixedLengthCRHGadget};
+
+use crate::wide_tree::{WideMerkleTree, WideMerkleTreeConfig, WideMerkleTreeDigest, WideMerkleTreePath, WideMerkleTreePathGadget};
+
+/// Sentinel value stored at every unoccupied slot of the sparse tree.
+pub const EMPTY_LEAF: [u8; 30] = [0u8; 30];
+
+/// The binary (`ARITY = 2`) wide-tree config `non_membership` and `batch`
+/// build their sparse/dense trees over. A plain `WideMerkleTreeConfig` with
+/// `ARITY = 2` rather than `crypto_primitives::merkle_tree::MerkleTreeConfig`
+/// so that paths expose the position bits these modules need to constrain.
+pub struct BinaryConfig<H>(std::marker::PhantomData<H>);
+impl<H: FixedLengthCRH> WideMerkleTreeConfig for BinaryConfig<H> {
+    const HEIGHT: usize = 5;
+    const ARITY: usize = 2;
+    type H = H;
+}
+
+/// Derives the slot a key is stored at by hashing it with the tree's CRH
+/// and reducing the digest's little-endian byte representation modulo the
+/// tree's capacity, `ARITY^HEIGHT`.
+pub fn index_for_key<C: WideMerkleTreeConfig>(parameters: &<C::H as FixedLengthCRH>::Parameters, key: &[u8]) -> usize
+where
+    <C::H as FixedLengthCRH>::Output: algebra::ToBytes,
+{
+    let digest = C::H::evaluate(parameters, key).unwrap();
+    let mut bytes = Vec::new();
+    digest.write(&mut bytes).unwrap();
+    let mut index = 0usize;
+    for (i, byte) in bytes.iter().enumerate().take(std::mem::size_of::<usize>()) {
+        index |= (*byte as usize) << (8 * i);
+    }
+    index % C::ARITY.pow(C::HEIGHT as u32)
+}
+
+/// Builds a sparse tree of `ARITY^HEIGHT` slots: `entries` populates some
+/// slots with real leaves, every other slot holds [`EMPTY_LEAF`].
+pub fn build_sparse_tree<C: WideMerkleTreeConfig>(
+    parameters: Rc<<C::H as FixedLengthCRH>::Parameters>,
+    entries: &[(usize, [u8; 30])],
+) -> WideMerkleTree<C>
+where
+    <C::H as FixedLengthCRH>::Output: algebra::ToBytes,
+{
+    let capacity = C::ARITY.pow(C::HEIGHT as u32);
+    let mut leaves = vec![EMPTY_LEAF; capacity];
+    for (index, leaf) in entries {
+        leaves[*index] = *leaf;
+    }
+    WideMerkleTree::<C>::new(parameters, &leaves)
+}
+
+/// NonMembershipCircuit proves that, for a given `key` and `root`, the slot
+/// `index_for_key(key)` of the sparse tree rooted at `root` holds
+/// [`EMPTY_LEAF`] — i.e. that `key` has no entry in the tree.
+pub struct NonMembershipCircuit<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> {
+    params: <C::H as FixedLengthCRH>::Parameters,
+    /// Part of instance or "public input"
+    key: Option<[u8; 30]>,
+    /// Part of instance or "public input"
+    root: Option<WideMerkleTreeDigest<C>>,
+    /// Part of witness or "private input": the authentication path to the
+    /// (empty) slot `index_for_key(key)`.
+    path: WideMerkleTreePath<C>,
+    _hash_gadget: std::marker::PhantomData<HG>,
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> NonMembershipCircuit<C, HG>
+where
+    WideMerkleTreeDigest<C>: Default,
+{
+    pub fn for_setup(params: <C::H as FixedLengthCRH>::Parameters) -> Self {
+        Self { params, key: None, root: None, path: WideMerkleTreePath::<C>::default(), _hash_gadget: std::marker::PhantomData }
+    }
+
+    pub fn for_proving(
+        params: <C::H as FixedLengthCRH>::Parameters,
+        key: [u8; 30],
+        root: WideMerkleTreeDigest<C>,
+        path: WideMerkleTreePath<C>,
+    ) -> Self {
+        Self { params, key: Some(key), root: Some(root), path, _hash_gadget: std::marker::PhantomData }
+    }
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> ConstraintSynthesizer<Fr> for NonMembershipCircuit<C, HG>
+where
+    HG::OutputGadget: CondSelectGadget<Fr> + ToBytesGadget<Fr>,
+{
+    fn generate_constraints<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let Self { params, key, root, path, .. } = self;
+
+        // Allocate the queried key as a witness: the public input is the
+        // packed field elements below, not the 240 raw bits, matching the
+        // multipacked public-input convention used elsewhere in this crate.
+        let key = UInt8::alloc_vec(&mut cs.ns(|| "Key"), &key.unwrap_or(EMPTY_LEAF))?;
+        let key_bits: Vec<Boolean> = key.iter().flat_map(|byte| byte.into_bits_le()).collect();
+        crate::multipack::pack_bits_as_input(&mut cs.ns(|| "Packed key"), &key_bits)?;
+
+        let root = HG::OutputGadget::alloc_input(&mut cs.ns(|| "Digest"), || root.ok_or(SynthesisError::AssignmentMissing))?;
+
+        let crh_parameters = HG::ParametersGadget::alloc(&mut cs.ns(|| "Parameters"), || Ok(params))?;
+
+        let path = WideMerkleTreePathGadget::<C, HG>::alloc(cs.ns(|| "Path"), &path)?;
+
+        // The empty leaf is a public constant, not a witness: non-membership
+        // is the statement that *this specific* value sits at the slot.
+        let empty_leaf = UInt8::constant_vec(&EMPTY_LEAF);
+
+        path.check_membership(cs.ns(|| "Check empty slot"), &crh_parameters, &root, &empty_leaf.as_slice())?;
+
+        // Bind the witnessed path's position to `key`: recompute
+        // `index_for_key(key)` in-circuit by hashing `key` with the tree's
+        // CRH and taking the low bits of the digest's little-endian byte
+        // representation — the in-circuit mirror of `index_for_key`'s
+        // `% ARITY^HEIGHT` reduction — and enforce it bit-for-bit against
+        // `path.index_bits()`. Without this, the path above could lead to
+        // *any* empty slot, not specifically the one `key` hashes to.
+        let key_digest = HG::check_evaluation_gadget(cs.ns(|| "hash key"), &crh_parameters, &key)?;
+        let digest_bits: Vec<Boolean> =
+            key_digest.to_bytes(cs.ns(|| "digest bytes"))?.iter().flat_map(|byte| byte.into_bits_le()).collect();
+        let claimed_index_bits = path.index_bits();
+        for (i, (computed, claimed)) in digest_bits.iter().zip(claimed_index_bits.iter()).enumerate() {
+            computed.enforce_equal(&mut cs.ns(|| format!("index bit {} matches", i)), claimed)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use algebra::fields::bls12_381::fr::Fr;
+use crypto_primitives::crh::FixedLengthCRHGadget;
+use r1cs_core::{ConstraintSystem, SynthesisError};
+use r1cs_std::fields::fp::FpGadget;
+use r1cs_std::prelude::*;
+
+use super::{PoseidonCRH, PoseidonParameters, FULL_ROUNDS, PARTIAL_ROUNDS, RATE, WIDTH};
+
+/// In-circuit counterpart of `PoseidonParameters`: every round constant and
+/// every entry of the MDS matrix, allocated as a gadget so it can be fed
+/// into the field arithmetic below.
+#[derive(Clone)]
+pub struct PoseidonCRHGadgetParameters<F: algebra::PrimeField> {
+    round_constants: Vec<[FpGadget<F>; WIDTH]>,
+    mds: [[FpGadget<F>; WIDTH]; WIDTH],
+}
+
+impl AllocGadget<PoseidonParameters<Fr>, Fr> for PoseidonCRHGadgetParameters<Fr> {
+    fn alloc<F, T, CS: ConstraintSystem<Fr>>(mut cs: CS, f: F) -> Result<Self, SynthesisError>
+    where
+        F: FnOnce() -> Result<T, SynthesisError>,
+        T: std::borrow::Borrow<PoseidonParameters<Fr>>,
+    {
+        let params = f()?;
+        let params = params.borrow();
+
+        let round_constants = params
+            .round_constants
+            .iter()
+            .enumerate()
+            .map(|(r, lane)| {
+                let mut out = array_init_zero();
+                for (i, c) in lane.iter().enumerate() {
+                    out[i] = FpGadget::alloc(cs.ns(|| format!("round constant {} {}", r, i)), || Ok(*c))?;
+                }
+                Ok(out)
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        let mut mds = array_init_zero_matrix();
+        for (i, row) in params.mds.iter().enumerate() {
+            for (j, entry) in row.iter().enumerate() {
+                mds[i][j] = FpGadget::alloc(cs.ns(|| format!("mds {} {}", i, j)), || Ok(*entry))?;
+            }
+        }
+
+        Ok(Self { round_constants, mds })
+    }
+
+    fn alloc_input<F, T, CS: ConstraintSystem<Fr>>(mut cs: CS, f: F) -> Result<Self, SynthesisError>
+    where
+        F: FnOnce() -> Result<T, SynthesisError>,
+        T: std::borrow::Borrow<PoseidonParameters<Fr>>,
+    {
+        let params = f()?;
+        let params = params.borrow();
+
+        let round_constants = params
+            .round_constants
+            .iter()
+            .enumerate()
+            .map(|(r, lane)| {
+                let mut out = array_init_zero();
+                for (i, c) in lane.iter().enumerate() {
+                    out[i] = FpGadget::alloc_input(cs.ns(|| format!("round constant {} {}", r, i)), || Ok(*c))?;
+                }
+                Ok(out)
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        let mut mds = array_init_zero_matrix();
+        for (i, row) in params.mds.iter().enumerate() {
+            for (j, entry) in row.iter().enumerate() {
+                mds[i][j] = FpGadget::alloc_input(cs.ns(|| format!("mds {} {}", i, j)), || Ok(*entry))?;
+            }
+        }
+
+        Ok(Self { round_constants, mds })
+    }
+}
+
+fn array_init_zero() -> [FpGadget<Fr>; WIDTH] {
+    // `FpGadget` has no `Copy`/`Default`, so the array has to be built by hand.
+    let mut v = Vec::with_capacity(WIDTH);
+    for _ in 0..WIDTH {
+        v.push(FpGadget::zero());
+    }
+    v.try_into().unwrap_or_else(|_| panic!("WIDTH mismatch"))
+}
+
+fn array_init_zero_matrix() -> [[FpGadget<Fr>; WIDTH]; WIDTH] {
+    let mut v = Vec::with_capacity(WIDTH);
+    for _ in 0..WIDTH {
+        v.push(array_init_zero());
+    }
+    v.try_into().unwrap_or_else(|_| panic!("WIDTH mismatch"))
+}
+
+pub struct PoseidonCRHGadget<F: algebra::PrimeField> {
+    _field: std::marker::PhantomData<F>,
+}
+
+impl FixedLengthCRHGadget<PoseidonCRH<Fr>, Fr> for PoseidonCRHGadget<Fr> {
+    type OutputGadget = FpGadget<Fr>;
+    type ParametersGadget = PoseidonCRHGadgetParameters<Fr>;
+
+    fn check_evaluation_gadget<CS: ConstraintSystem<Fr>>(
+        mut cs: CS,
+        parameters: &Self::ParametersGadget,
+        input: &[UInt8],
+    ) -> Result<Self::OutputGadget, SynthesisError> {
+        let bits: Vec<Boolean> = input.iter().flat_map(|byte| byte.into_bits_le()).collect();
+        let elems = crate::multipack::pack_bits(cs.ns(|| "pack input bits"), &bits)?;
+
+        let mut state: Vec<FpGadget<Fr>> = (0..WIDTH).map(|_| FpGadget::zero()).collect();
+        for (chunk_i, chunk) in elems.chunks(RATE).enumerate() {
+            for (i, e) in chunk.iter().enumerate() {
+                state[i] = state[i].add(cs.ns(|| format!("absorb {} {}", chunk_i, i)), e)?;
+            }
+            state = permute(cs.ns(|| format!("permute {}", chunk_i)), parameters, state)?;
+        }
+        Ok(state[0].clone())
+    }
+}
+
+fn permute<CS: ConstraintSystem<Fr>>(
+    mut cs: CS,
+    parameters: &PoseidonCRHGadgetParameters<Fr>,
+    mut state: Vec<FpGadget<Fr>>,
+) -> Result<Vec<FpGadget<Fr>>, SynthesisError> {
+    for round in 0..(FULL_ROUNDS + PARTIAL_ROUNDS) {
+        for (i, x) in state.iter_mut().enumerate() {
+            *x = x.add(cs.ns(|| format!("round {} add constant {}", round, i)), &parameters.round_constants[round][i])?;
+        }
+
+        let is_full_round = round < FULL_ROUNDS / 2 || round >= FULL_ROUNDS / 2 + PARTIAL_ROUNDS;
+        if is_full_round {
+            for (i, x) in state.iter_mut().enumerate() {
+                *x = sbox(cs.ns(|| format!("round {} sbox {}", round, i)), x)?;
+            }
+        } else {
+            state[0] = sbox(cs.ns(|| format!("round {} sbox 0", round)), &state[0])?;
+        }
+
+        let mut new_state = Vec::with_capacity(WIDTH);
+        for i in 0..WIDTH {
+            let mut acc = FpGadget::zero();
+            for j in 0..WIDTH {
+                let term = state[j].mul(cs.ns(|| format!("round {} mds {} {}", round, i, j)), &parameters.mds[i][j])?;
+                acc = acc.add(cs.ns(|| format!("round {} mds sum {} {}", round, i, j)), &term)?;
+            }
+            new_state.push(acc);
+        }
+        state = new_state;
+    }
+    Ok(state)
+}
+
+/// `x^5`, computed as `(x^2)^2 * x` to keep the constraint count at three
+/// multiplications per S-box invocation.
+fn sbox<CS: ConstraintSystem<Fr>>(mut cs: CS, x: &FpGadget<Fr>) -> Result<FpGadget<Fr>, SynthesisError> {
+    let x2 = x.mul(cs.ns(|| "x^2"), x)?;
+    let x4 = x2.mul(cs.ns(|| "x^4"), &x2)?;
+    x4.mul(cs.ns(|| "x^5"), x)
+}
@@ -0,0 +1,132 @@
+//! A Poseidon-based `FixedLengthCRH` over `Fr`.
+//!
+//! Unlike `PedersenCRH`, which operates over a twisted Edwards curve and
+//! therefore needs its input bit-decomposed onto the curve, Poseidon is an
+//! algebraic hash that operates directly on the SNARK's native field. That
+//! makes it dramatically cheaper to verify in-circuit (see
+//! `constraints.rs`): no bit decomposition, just field additions,
+//! `x^5` S-boxes and a `WIDTH x WIDTH` MDS multiply per round. The MDS
+//! entries are allocated as witnesses, so each of those products is a
+//! multiplication constraint rather than a linear combination.
+//!
+//! This is a toy, tutorial-grade instantiation: the round constants and MDS
+//! matrix are generated pseudorandomly in `setup` rather than derived via a
+//! Grain LFSR / Cauchy matrix as a production instantiation would be. The
+//! round schedule and width are fixed (a rate-2 sponge); longer inputs,
+//! such as the `ARITY` children of a `wide_tree` node, are absorbed over
+//! several permutations.
+
+use algebra::fields::bls12_381::fr::Fr;
+use algebra::{Field, PrimeField};
+use crypto_primitives::crh::FixedLengthCRH;
+use rand::Rng;
+use std::marker::PhantomData;
+
+pub mod constraints;
+
+/// Sponge width: `RATE` field elements of input per permutation, plus one
+/// element of capacity.
+pub const RATE: usize = 2;
+pub const WIDTH: usize = RATE + 1;
+
+/// Number of full rounds (split evenly before/after the partial rounds) and
+/// partial rounds, following the standard Poseidon round schedule.
+pub const FULL_ROUNDS: usize = 8;
+pub const PARTIAL_ROUNDS: usize = 57;
+
+#[derive(Clone)]
+pub struct PoseidonParameters<F: PrimeField> {
+    /// `round_constants[r][i]` is the constant added to lane `i` in round `r`.
+    pub round_constants: Vec<[F; WIDTH]>,
+    /// The `WIDTH x WIDTH` MDS matrix applied at the end of every round.
+    pub mds: [[F; WIDTH]; WIDTH],
+}
+
+impl<F: PrimeField> PoseidonParameters<F> {
+    fn num_rounds() -> usize {
+        FULL_ROUNDS + PARTIAL_ROUNDS
+    }
+
+    /// Applies the S-box `x^5` to `state`, to every lane during a full round
+    /// and only to lane `0` during a partial round.
+    fn apply_sbox(state: &mut [F; WIDTH], is_full_round: bool) {
+        if is_full_round {
+            for x in state.iter_mut() {
+                *x = x.pow([5u64]);
+            }
+        } else {
+            state[0] = state[0].pow([5u64]);
+        }
+    }
+
+    fn apply_mds(&self, state: &[F; WIDTH]) -> [F; WIDTH] {
+        let mut new_state = [F::zero(); WIDTH];
+        for (i, row) in self.mds.iter().enumerate() {
+            let mut acc = F::zero();
+            for (entry, s) in row.iter().zip(state.iter()) {
+                acc += &(*entry * s);
+            }
+            new_state[i] = acc;
+        }
+        new_state
+    }
+
+    /// Runs the full Poseidon permutation in place over `state`.
+    pub fn permute(&self, state: &mut [F; WIDTH]) {
+        for round in 0..Self::num_rounds() {
+            for (x, c) in state.iter_mut().zip(self.round_constants[round].iter()) {
+                *x += c;
+            }
+            let is_full_round = round < FULL_ROUNDS / 2 || round >= FULL_ROUNDS / 2 + PARTIAL_ROUNDS;
+            Self::apply_sbox(state, is_full_round);
+            *state = self.apply_mds(state);
+        }
+    }
+}
+
+/// A Poseidon sponge, instantiated as a `FixedLengthCRH` so it can be used
+/// as a drop-in replacement for `PedersenCRH` in a `MerkleTreeConfig`.
+pub struct PoseidonCRH<F: PrimeField> {
+    _field: PhantomData<F>,
+}
+
+impl FixedLengthCRH for PoseidonCRH<Fr> {
+    // The upstream binary Merkle tree hashes two 32-byte digests (or a leaf)
+    // at a time; the sponge itself absorbs input of any length, which the
+    // `wide_tree` trees rely on when they hash `ARITY` digests per node.
+    const INPUT_SIZE_BITS: usize = 512;
+    type Output = Fr;
+    type Parameters = PoseidonParameters<Fr>;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, crypto_primitives::Error> {
+        let round_constants = (0..PoseidonParameters::<Fr>::num_rounds())
+            .map(|_| {
+                let mut lane = [Fr::zero(); WIDTH];
+                for x in lane.iter_mut() {
+                    *x = rng.gen();
+                }
+                lane
+            })
+            .collect();
+        let mut mds = [[Fr::zero(); WIDTH]; WIDTH];
+        for row in mds.iter_mut() {
+            for entry in row.iter_mut() {
+                *entry = rng.gen();
+            }
+        }
+        Ok(PoseidonParameters { round_constants, mds })
+    }
+
+    fn evaluate(parameters: &Self::Parameters, input: &[u8]) -> Result<Self::Output, crypto_primitives::Error> {
+        let elems = crate::multipack::pack_bytes(input);
+
+        let mut state = [Fr::zero(); WIDTH];
+        for chunk in elems.chunks(RATE) {
+            for (s, e) in state.iter_mut().zip(chunk.iter()) {
+                *s += e;
+            }
+            parameters.permute(&mut state);
+        }
+        Ok(state[0])
+    }
+}
@@ -0,0 +1,358 @@
+//! Wide (k-ary) Merkle trees.
+//!
+//! `crypto_primitives::merkle_tree::MerkleTreeConfig` (used everywhere else
+//! in this crate) only has a `HEIGHT` const and hashes two children per
+//! node — it bakes arity 2 into `MerkleHashTree`/`MerkleTreePathGadget`
+//! themselves, which live upstream in the `crypto_primitives` crate, not
+//! here. Rather than fork that crate, this module defines a parallel
+//! `WideMerkleTreeConfig` with an `ARITY` const and its own tree/path/gadget
+//! types that generalize the same algorithm to `ARITY` children per node.
+//! Everything in `main` that wants a wide tree goes through this module
+//! instead of `crypto_primitives::merkle_tree`.
+//!
+//! A wider tree means fewer levels for the same leaf count, and therefore
+//! fewer (more expensive, but still O(log_ARITY(n))) CRH invocations on the
+//! authentication path.
+
+use std::rc::Rc;
+
+use algebra::fields::bls12_381::fr::Fr;
+use r1cs_core::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use r1cs_std::prelude::*;
+
+use crypto_primitives::crh::{FixedLengthCRH, FixedLengthCRHGadget};
+
+pub trait WideMerkleTreeConfig {
+    const HEIGHT: usize;
+    const ARITY: usize;
+    type H: FixedLengthCRH;
+}
+
+/// Mirrors `crypto_primitives::merkle_tree::MerkleTreeDigest`, for the same
+/// readability reasons, now that other modules (`non_membership`, `batch`)
+/// need to name a `WideMerkleTreeConfig`'s root type.
+pub type WideMerkleTreeDigest<C> = <<C as WideMerkleTreeConfig>::H as FixedLengthCRH>::Output;
+
+/// The authentication path for one leaf: per level, the `ARITY - 1`
+/// siblings of the node on the path, and the node's position (`0..ARITY`)
+/// among its siblings.
+#[derive(Clone)]
+pub struct WideMerkleTreePath<C: WideMerkleTreeConfig> {
+    pub path: Vec<(Vec<<C::H as FixedLengthCRH>::Output>, usize)>,
+}
+
+impl<C: WideMerkleTreeConfig> Default for WideMerkleTreePath<C>
+where
+    <C::H as FixedLengthCRH>::Output: Default,
+{
+    fn default() -> Self {
+        let sibling = <C::H as FixedLengthCRH>::Output::default();
+        Self {
+            path: (0..C::HEIGHT)
+                .map(|_| (vec![sibling.clone(); C::ARITY - 1], 0))
+                .collect(),
+        }
+    }
+}
+
+pub struct WideMerkleTree<C: WideMerkleTreeConfig> {
+    parameters: Rc<<C::H as FixedLengthCRH>::Parameters>,
+    /// `levels[0]` are the leaf digests, `levels[HEIGHT]` is `[root]`.
+    levels: Vec<Vec<<C::H as FixedLengthCRH>::Output>>,
+}
+
+fn hash_children<C: WideMerkleTreeConfig>(
+    parameters: &<C::H as FixedLengthCRH>::Parameters,
+    children: &[<C::H as FixedLengthCRH>::Output],
+) -> <C::H as FixedLengthCRH>::Output
+where
+    <C::H as FixedLengthCRH>::Output: algebra::ToBytes,
+{
+    let mut bytes = Vec::new();
+    for child in children {
+        child.write(&mut bytes).unwrap();
+    }
+    C::H::evaluate(parameters, &bytes).unwrap()
+}
+
+impl<C: WideMerkleTreeConfig> WideMerkleTree<C>
+where
+    <C::H as FixedLengthCRH>::Output: algebra::ToBytes,
+{
+    pub fn new(parameters: Rc<<C::H as FixedLengthCRH>::Parameters>, leaves: &[[u8; 30]]) -> Self {
+        assert_eq!(leaves.len(), C::ARITY.pow(C::HEIGHT as u32), "leaf count must equal ARITY^HEIGHT");
+
+        let mut levels = Vec::with_capacity(C::HEIGHT + 1);
+        levels.push(
+            leaves
+                .iter()
+                .map(|leaf| C::H::evaluate(&parameters, leaf).unwrap())
+                .collect::<Vec<_>>(),
+        );
+        for _ in 0..C::HEIGHT {
+            let below = levels.last().unwrap();
+            let above = below
+                .chunks(C::ARITY)
+                .map(|siblings| hash_children::<C>(&parameters, siblings))
+                .collect();
+            levels.push(above);
+        }
+        Self { parameters, levels }
+    }
+
+    pub fn root(&self) -> <C::H as FixedLengthCRH>::Output {
+        self.levels.last().unwrap()[0].clone()
+    }
+
+    pub fn generate_proof(&self, leaf_index: usize) -> WideMerkleTreePath<C> {
+        let mut path = Vec::with_capacity(C::HEIGHT);
+        let mut index = leaf_index;
+        for level in &self.levels[..C::HEIGHT] {
+            let node_index = index / C::ARITY;
+            let position = index % C::ARITY;
+            let siblings = level[node_index * C::ARITY..(node_index + 1) * C::ARITY]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != position)
+                .map(|(_, digest)| digest.clone())
+                .collect();
+            path.push((siblings, position));
+            index = node_index;
+        }
+        WideMerkleTreePath { path }
+    }
+}
+
+impl<C: WideMerkleTreeConfig> WideMerkleTreePath<C>
+where
+    <C::H as FixedLengthCRH>::Output: algebra::ToBytes + Eq,
+{
+    pub fn verify(
+        &self,
+        parameters: &<C::H as FixedLengthCRH>::Parameters,
+        root: &<C::H as FixedLengthCRH>::Output,
+        leaf: &[u8; 30],
+    ) -> bool {
+        let mut current = C::H::evaluate(parameters, leaf).unwrap();
+        for (siblings, position) in &self.path {
+            let children = insert_at(siblings, *position, current);
+            current = hash_children::<C>(parameters, &children);
+        }
+        current == *root
+    }
+}
+
+fn insert_at<T: Clone>(siblings: &[T], position: usize, value: T) -> Vec<T> {
+    let mut out = siblings.to_vec();
+    out.insert(position, value);
+    out
+}
+
+/// Smallest number of bits that can represent every value in `0..arity`.
+pub(crate) fn bits_for_arity(arity: usize) -> usize {
+    let mut bits = 0;
+    while (1usize << bits) < arity {
+        bits += 1;
+    }
+    bits
+}
+
+/// In-circuit counterpart of `WideMerkleTreePath`.
+pub struct WideMerkleTreePathGadget<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> {
+    /// Per level: the `ARITY - 1` witnessed siblings, and the position's
+    /// bits (little-endian, `ceil(log2(ARITY))` of them).
+    path: Vec<(Vec<HG::OutputGadget>, Vec<Boolean>)>,
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> WideMerkleTreePathGadget<C, HG> {
+    pub fn alloc<CS: ConstraintSystem<Fr>>(mut cs: CS, path: &WideMerkleTreePath<C>) -> Result<Self, SynthesisError> {
+        let position_bits = bits_for_arity(C::ARITY);
+
+        let path = path
+            .path
+            .iter()
+            .enumerate()
+            .map(|(level, (siblings, position))| {
+                let mut level_cs = cs.ns(|| format!("level {}", level));
+                let siblings = siblings
+                    .iter()
+                    .enumerate()
+                    .map(|(i, sibling)| {
+                        HG::OutputGadget::alloc(level_cs.ns(|| format!("sibling {}", i)), || Ok(sibling.clone()))
+                    })
+                    .collect::<Result<Vec<_>, SynthesisError>>()?;
+                let bits = (0..position_bits)
+                    .map(|i| {
+                        Boolean::alloc(level_cs.ns(|| format!("position bit {}", i)), || Ok((position >> i) & 1 == 1))
+                    })
+                    .collect::<Result<Vec<_>, SynthesisError>>()?;
+                Ok((siblings, bits))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+        Ok(Self { path })
+    }
+
+    /// The claimed leaf index, as bits: per level, `bits_for_arity(ARITY)`
+    /// position bits, least-significant level first. Since `ARITY` is a
+    /// power of two for every config in this module, this is exactly the
+    /// little-endian binary representation of the leaf's index among
+    /// `ARITY^HEIGHT` slots — callers that derive an index some other way
+    /// (e.g. `non_membership::index_for_key`) can bind it to this path by
+    /// enforcing bit-for-bit equality against their own little-endian bits.
+    pub(crate) fn index_bits(&self) -> Vec<Boolean> {
+        self.path.iter().flat_map(|(_, bits)| bits.clone()).collect()
+    }
+
+    /// Recomputes the root from `leaf` along this path and enforces that it
+    /// equals `root`.
+    pub fn check_membership<CS: ConstraintSystem<Fr>>(
+        &self,
+        mut cs: CS,
+        parameters: &HG::ParametersGadget,
+        root: &HG::OutputGadget,
+        leaf: &[UInt8],
+    ) -> Result<(), SynthesisError>
+    where
+        HG::OutputGadget: CondSelectGadget<Fr>,
+    {
+        let mut current = HG::check_evaluation_gadget(cs.ns(|| "hash leaf"), parameters, leaf)?;
+
+        for (level, (siblings, position_bits)) in self.path.iter().enumerate() {
+            let mut level_cs = cs.ns(|| format!("level {}", level));
+            let ordered = insert_at_index_gadget::<C, HG, _>(level_cs.ns(|| "insert"), siblings, position_bits, &current)?;
+
+            let mut bytes = Vec::new();
+            for child in &ordered {
+                bytes.extend_from_slice(&child.to_bytes(level_cs.ns(|| "to_bytes"))?);
+            }
+            current = HG::check_evaluation_gadget(level_cs.ns(|| "hash level"), parameters, &bytes)?;
+        }
+
+        current.enforce_equal(&mut cs.ns(|| "root matches"), root)
+    }
+}
+
+/// Builds the ordered `ARITY`-length child vector by inserting `current` at
+/// the position given by `position_bits` among `siblings`, using only
+/// conditional selects (no branching on a secret position).
+fn insert_at_index_gadget<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>, CS: ConstraintSystem<Fr>>(
+    mut cs: CS,
+    siblings: &[HG::OutputGadget],
+    position_bits: &[Boolean],
+    current: &HG::OutputGadget,
+) -> Result<Vec<HG::OutputGadget>, SynthesisError>
+where
+    HG::OutputGadget: CondSelectGadget<Fr>,
+{
+    // One indicator bit per possible position, `is_position[k] == (position == k)`.
+    let is_position = (0..C::ARITY)
+        .map(|k| {
+            let eq_bits = position_bits
+                .iter()
+                .enumerate()
+                .map(|(i, bit)| if (k >> i) & 1 == 1 { bit.clone() } else { bit.not() })
+                .collect::<Vec<_>>();
+            Boolean::kary_and(&eq_bits)
+        })
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+    (0..C::ARITY)
+        .map(|slot| {
+            // Candidate value for `slot` if the true position were `k`:
+            // `current` when `k == slot`, else the sibling that lands in
+            // `slot` once `current` is inserted at `k`. Exactly
+            // one `k` is selected by `is_position`, so the initial value
+            // below is never observed.
+            let mut value = current.clone();
+            for k in 0..C::ARITY {
+                let candidate = if k == slot {
+                    current.clone()
+                } else {
+                    // `siblings` skips the true position `k`, so the child
+                    // at output `slot` is `siblings[slot]` when `slot` comes
+                    // before `k`, else `siblings[slot - 1]`.
+                    let sibling_index = if slot < k { slot } else { slot - 1 };
+                    siblings[sibling_index].clone()
+                };
+                value = HG::OutputGadget::conditionally_select(
+                    cs.ns(|| format!("select slot {} candidate {}", slot, k)),
+                    &is_position[k],
+                    &candidate,
+                    &value,
+                )?;
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+/// Generic membership circuit for any `WideMerkleTreeConfig`, mirroring
+/// `main`'s `PathCheckCircuit` for the upstream, arity-fixed Merkle tree.
+/// Needed because `PathCheckCircuit` is bound to
+/// `crypto_primitives::merkle_tree::MerkleTreeConfig`, which a
+/// `WideMerkleTreeConfig` tree doesn't implement.
+pub struct WidePathCheckCircuit<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> {
+    params: <C::H as FixedLengthCRH>::Parameters,
+    /// Part of instance or "public input"
+    leaf: Option<[u8; 30]>,
+    /// Part of instance or "public input"
+    root: Option<WideMerkleTreeDigest<C>>,
+    /// Part of witness or "private input"
+    path: WideMerkleTreePath<C>,
+    _hash_gadget: std::marker::PhantomData<HG>,
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> WidePathCheckCircuit<C, HG>
+where
+    WideMerkleTreeDigest<C>: Default,
+{
+    pub fn for_setup(params: <C::H as FixedLengthCRH>::Parameters) -> Self {
+        Self { params, leaf: None, root: None, path: WideMerkleTreePath::<C>::default(), _hash_gadget: std::marker::PhantomData }
+    }
+
+    pub fn for_proving(
+        params: <C::H as FixedLengthCRH>::Parameters,
+        leaf: [u8; 30],
+        root: WideMerkleTreeDigest<C>,
+        path: WideMerkleTreePath<C>,
+    ) -> Self {
+        Self { params, leaf: Some(leaf), root: Some(root), path, _hash_gadget: std::marker::PhantomData }
+    }
+}
+
+impl<C: WideMerkleTreeConfig, HG: FixedLengthCRHGadget<C::H, Fr>> ConstraintSynthesizer<Fr> for WidePathCheckCircuit<C, HG>
+where
+    HG::OutputGadget: CondSelectGadget<Fr>,
+{
+    fn generate_constraints<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let Self { params, leaf, root, path, .. } = self;
+
+        let leaf = UInt8::alloc_vec(&mut cs.ns(|| "Leaf"), &leaf.unwrap_or([0u8; 30]))?;
+        let leaf_bits: Vec<Boolean> = leaf.iter().flat_map(|byte| byte.into_bits_le()).collect();
+        crate::multipack::pack_bits_as_input(&mut cs.ns(|| "Packed leaf"), &leaf_bits)?;
+
+        let root = HG::OutputGadget::alloc_input(&mut cs.ns(|| "Digest"), || root.ok_or(SynthesisError::AssignmentMissing))?;
+        let crh_parameters = HG::ParametersGadget::alloc(&mut cs.ns(|| "Parameters"), || Ok(params))?;
+        let path = WideMerkleTreePathGadget::<C, HG>::alloc(cs.ns(|| "Path"), &path)?;
+
+        path.check_membership(cs.ns(|| "Check membership"), &crh_parameters, &root, &leaf.as_slice())?;
+        Ok(())
+    }
+}
+
+/// Arity-4 config: `H::evaluate` over a JubJub-Pedersen-style or
+/// Poseidon-style CRH, 4 children per node.
+pub struct Arity4Config<H>(std::marker::PhantomData<H>);
+impl<H: FixedLengthCRH> WideMerkleTreeConfig for Arity4Config<H> {
+    const HEIGHT: usize = 3;
+    const ARITY: usize = 4;
+    type H = H;
+}
+
+/// Arity-8 config: 8 children per node.
+pub struct Arity8Config<H>(std::marker::PhantomData<H>);
+impl<H: FixedLengthCRH> WideMerkleTreeConfig for Arity8Config<H> {
+    const HEIGHT: usize = 2;
+    const ARITY: usize = 8;
+    type H = H;
+}